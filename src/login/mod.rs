@@ -1,26 +1,28 @@
 use oauth2::basic::BasicClient;
-use oauth2::reqwest::http_client;
+use oauth2::reqwest::{async_http_client, http_client};
 
 use oauth2::{
     AuthType, AuthUrl, AuthorizationCode, ClientId, CsrfToken, PkceCodeChallenge, RedirectUrl,
-    Scope, TokenResponse, TokenUrl,
+    RefreshToken, RevocationUrl, Scope, StandardRevocableToken, TokenResponse, TokenUrl,
 };
 
 use std::collections::HashSet;
 use std::env; // TODO: remove
 use std::iter::FromIterator;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use hyper::server::conn::AddrStream;
 use hyper::service::{make_service_fn, service_fn};
 use hyper::{Body, Request, Response, Server, StatusCode};
 
 use anyhow::Result;
-use futures::executor::block_on;
-use tokio::sync::mpsc;
+use serde::Deserialize;
+use tokio::sync::{mpsc, oneshot};
 
 use crate::terminal::{interactive, open_browser};
 
-use crate::commands::config::global_config;
+use crate::commands::config::{delete_global_config, get_global_config, global_config};
 use crate::settings::global_user::{GlobalUser, TokenType};
 
 // List of allowed scopes for OAuth
@@ -35,8 +37,55 @@ static SCOPES_LIST: [&str; 8] = [
     "zone:read",
 ];
 
-// HTTP Server request handler
-async fn handle_callback(req: Request<Body>, tx: mpsc::Sender<String>) -> Result<Response<Body>> {
+// Treat a token as expired once it has fewer than this many seconds of life left, so
+// callers refresh ahead of time instead of racing an API call against expiry.
+const MIN_TIME_LEFT: i64 = 60;
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("system clock is before the Unix epoch")
+        .as_secs()
+}
+
+// Returns true once fewer than `MIN_TIME_LEFT` seconds remain before `expires_at` (or it's
+// already past); exactly `MIN_TIME_LEFT` seconds left still counts as live.
+pub fn token_is_expired(expires_at: u64) -> bool {
+    let now = unix_now() as i64;
+    expires_at as i64 - now < MIN_TIME_LEFT
+}
+
+#[cfg(test)]
+mod token_expiry_tests {
+    use super::*;
+
+    #[test]
+    fn not_expired_with_more_than_min_time_left() {
+        let expires_at = unix_now() + (MIN_TIME_LEFT as u64) + 1;
+        assert!(!token_is_expired(expires_at));
+    }
+
+    #[test]
+    fn not_expired_right_at_the_min_time_left_boundary() {
+        let expires_at = unix_now() + (MIN_TIME_LEFT as u64);
+        assert!(!token_is_expired(expires_at));
+    }
+
+    #[test]
+    fn expired_when_already_in_the_past() {
+        let expires_at = unix_now() - 1;
+        assert!(token_is_expired(expires_at));
+    }
+}
+
+// HTTP Server request handler. `port` is the actual OS-assigned port the callback
+// server is bound to, so the consent redirect points back at this server instead of
+// a stale, unrelated address.
+async fn handle_callback(
+    req: Request<Body>,
+    tx: mpsc::Sender<String>,
+    port: u16,
+) -> Result<Response<Body>> {
     match req.uri().path() {
         // Endpoint given when registering oauth client
         "/oauth/callback" => {
@@ -63,7 +112,10 @@ async fn handle_callback(req: Request<Body>, tx: mpsc::Sender<String>) -> Result
                 let response = Response::builder()
                     .status(StatusCode::PERMANENT_REDIRECT)
                     //.header("Location", "https://welcome.developers.workers.dev")
-                    .header("Location", "http://127.0.0.1:8787/wrangler-oauth-consent-denied")
+                    .header(
+                        "Location",
+                        format!("http://127.0.0.1:{}/wrangler-oauth-consent-denied", port),
+                    )
                     .body(Body::empty())
                     .unwrap();
                 return Ok(response);
@@ -76,7 +128,10 @@ async fn handle_callback(req: Request<Body>, tx: mpsc::Sender<String>) -> Result
             let response = Response::builder()
                 .status(StatusCode::PERMANENT_REDIRECT)
                 //.header("Location", "https://welcome.developers.workers.dev")
-                .header("Location", "http://127.0.0.1:8787/wrangler-oauth-consent-granted")
+                .header(
+                    "Location",
+                    format!("http://127.0.0.1:{}/wrangler-oauth-consent-granted", port),
+                )
                 .body(Body::empty())
                 .unwrap();
 
@@ -96,87 +151,285 @@ async fn handle_callback(req: Request<Body>, tx: mpsc::Sender<String>) -> Result
     }
 }
 
-// Get results (i.e. authorization code and CSRF state) back from local HTTP server
-async fn http_server_get_params() -> Result<String> {
-    let (tx, mut rx) = tokio::sync::mpsc::channel::<String>(1);
-
-    // Create and start listening for authorization redirect on local HTTP server
-    let server_fn_gen = |tx: mpsc::Sender<String>| {
-        service_fn(move |req: Request<Body>| {
-            let tx_clone = tx.clone();
-            handle_callback(req, tx_clone)
-        })
-    };
+// Bind an OS-assigned free port for the OAuth redirect callback, returning the raw
+// listener (to hand to hyper) and the port it was given so callers can build the
+// `RedirectUrl` before the authorize URL is constructed.
+fn bind_callback_listener() -> Result<(std::net::TcpListener, u16)> {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+    listener.set_nonblocking(true)?;
+    let port = listener.local_addr()?.port();
+    Ok((listener, port))
+}
 
-    let service = make_service_fn(move |_socket: &AddrStream| {
-        let tx_clone = tx.clone();
-        async move { Ok::<_, hyper::Error>(server_fn_gen(tx_clone)) }
+// Serve the OAuth redirect callback on `listener` until the single expected request
+// arrives, then shut the server down via a oneshot rather than leaving it running as
+// a detached task. Runs on the caller's existing async runtime.
+async fn http_server_get_params(listener: std::net::TcpListener, port: u16) -> Result<String> {
+    let (tx, mut rx) = mpsc::channel::<String>(1);
+    let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+    let shutdown_tx = Arc::new(Mutex::new(Some(shutdown_tx)));
+
+    let make_svc = make_service_fn(move |_socket: &AddrStream| {
+        let tx = tx.clone();
+        let shutdown_tx = shutdown_tx.clone();
+        async move {
+            Ok::<_, hyper::Error>(service_fn(move |req: Request<Body>| {
+                let tx = tx.clone();
+                let shutdown_tx = shutdown_tx.clone();
+                async move {
+                    let response = handle_callback(req, tx, port).await;
+                    // Only one redirect is ever expected; tear the server down once
+                    // it's been handled instead of leaking a detached listener task.
+                    if let Some(shutdown_tx) = shutdown_tx.lock().unwrap().take() {
+                        let _ = shutdown_tx.send(());
+                    }
+                    response
+                }
+            }))
+        }
     });
 
-    let runtime = tokio::runtime::Runtime::new()?;
-    runtime.spawn(async {
-        let addr = ([127, 0, 0, 1], 8976).into();
-
-        let server = Server::bind(&addr).serve(service);
-        server.await.unwrap();
-    });
+    let server = Server::from_tcp(listener)?
+        .serve(make_svc)
+        .with_graceful_shutdown(async {
+            shutdown_rx.await.ok();
+        });
+    server.await?;
 
-    // Receive authorization code and csrf state from HTTP server
-    let params = runtime.block_on(async { rx.recv().await.unwrap() });
-    Ok(params)
+    rx.recv()
+        .await
+        .ok_or_else(|| anyhow::anyhow!("Local HTTP server closed without receiving a callback"))
 }
 
-pub fn run(scopes: Option<&[&str]>) -> Result<()> {
-    // -------------------------
-    // Temporary authentication
-    // TODO: Remove when ready
+// -------------------------
+// Temporary authentication
+// TODO: Remove when ready
+fn get_client_id() -> String {
     let env_key = "CLIENT_ID";
-    let client_id = match env::var(env_key) {
+    match env::var(env_key) {
         Ok(value) => value,
         Err(_) => panic!("client_id not provided"),
+    }
+}
+// -------------------------
+
+// Base URL of the Cloudflare OAuth authorization server. Defaults to staging; set
+// `WRANGLER_OAUTH_ENV=production` to point wrangler at the production server without
+// a recompile.
+fn oauth_base_url() -> String {
+    match env::var("WRANGLER_OAUTH_ENV").as_deref() {
+        Ok("production") => "https://dash.cloudflare.com".to_string(),
+        _ => "https://dash.staging.cloudflare.com".to_string(),
+    }
+}
+
+// The subset of RFC 8414 authorization server metadata wrangler needs to build its
+// oauth2 client and to sanity-check the scopes it's about to request.
+#[derive(Deserialize)]
+struct AuthServerMetadata {
+    authorization_endpoint: String,
+    token_endpoint: String,
+    revocation_endpoint: Option<String>,
+    introspection_endpoint: Option<String>,
+    #[serde(default)]
+    scopes_supported: Vec<String>,
+}
+
+// Fetch and parse `/.well-known/oauth-authorization-server` so production vs. staging,
+// and any endpoint changes on Cloudflare's side, don't require a wrangler release.
+// Used by the sync call sites (`logout`, `introspect_token`) where a blocking request
+// doesn't risk stalling an async executor. Async callers (`run`, `refresh_token`) use
+// `discover_metadata_async` instead.
+fn discover_metadata() -> Result<AuthServerMetadata> {
+    let url = format!(
+        "{}/.well-known/oauth-authorization-server",
+        oauth_base_url()
+    );
+    let metadata = reqwest::blocking::get(&url)?
+        .error_for_status()?
+        .json::<AuthServerMetadata>()?;
+    Ok(metadata)
+}
+
+// Async counterpart of `discover_metadata`, for `run`'s fully-async flow so discovery
+// doesn't block the executor thread for the round trip.
+async fn discover_metadata_async() -> Result<AuthServerMetadata> {
+    let url = format!(
+        "{}/.well-known/oauth-authorization-server",
+        oauth_base_url()
+    );
+    let metadata = reqwest::get(&url)
+        .await?
+        .error_for_status()?
+        .json::<AuthServerMetadata>()
+        .await?;
+    Ok(metadata)
+}
+
+// Error out early if wrangler is configured to request a scope the server doesn't
+// advertise, rather than letting the authorize redirect fail opaquely.
+fn validate_scopes(metadata: &AuthServerMetadata, scopes: &[&str]) -> Result<()> {
+    if metadata.scopes_supported.is_empty() {
+        // Server doesn't publish this (optional) field; nothing to validate against.
+        return Ok(());
+    }
+    let supported: HashSet<&str> = metadata
+        .scopes_supported
+        .iter()
+        .map(String::as_str)
+        .collect();
+    for scope in scopes {
+        if !supported.contains(scope) {
+            anyhow::bail!(
+                "Wrangler requests the `{}` scope, but {} does not advertise support for it",
+                scope,
+                oauth_base_url()
+            );
+        }
+    }
+    Ok(())
+}
+
+// Scopes already granted to the stored token, if any. An absent or non-OAuth config
+// simply means there's nothing to merge with yet.
+fn previously_granted_scopes() -> HashSet<String> {
+    match get_global_config() {
+        Ok(GlobalUser::TokenAuth {
+            token_type: TokenType::Oauth { granted_scopes, .. },
+            ..
+        }) => granted_scopes.into_iter().collect(),
+        _ => HashSet::new(),
+    }
+}
+
+/// The result of comparing a set of requested scopes against what's already granted,
+/// so `wrangler login --scopes` can tell the user which scopes will actually widen
+/// their session.
+pub struct ScopeDiff {
+    pub newly_requested: Vec<String>,
+    pub already_granted: Vec<String>,
+}
+
+/// Compare `requested` against the scopes already held by the stored token.
+pub fn diff_requested_scopes(requested: &[&str]) -> ScopeDiff {
+    diff_scopes(requested, &previously_granted_scopes())
+}
+
+// Pure split of `requested` into already-granted vs. newly-requested, kept separate from
+// `diff_requested_scopes` so the comparison logic is testable without a global config.
+fn diff_scopes(requested: &[&str], granted: &HashSet<String>) -> ScopeDiff {
+    let mut diff = ScopeDiff {
+        newly_requested: Vec::new(),
+        already_granted: Vec::new(),
     };
+    for scope in requested {
+        if granted.contains(*scope) {
+            diff.already_granted.push(scope.to_string());
+        } else {
+            diff.newly_requested.push(scope.to_string());
+        }
+    }
+    diff
+}
+
+#[cfg(test)]
+mod scope_diff_tests {
+    use super::*;
 
-    // -------------------------
+    #[test]
+    fn splits_already_granted_from_newly_requested() {
+        let granted: HashSet<String> = HashSet::from_iter(vec!["account:read".to_string()]);
+        let diff = diff_scopes(&["account:read", "workers:write"], &granted);
+
+        assert_eq!(diff.already_granted, vec!["account:read".to_string()]);
+        assert_eq!(diff.newly_requested, vec!["workers:write".to_string()]);
+    }
 
-    // Create oauth2 client
-    let client = BasicClient::new(
-        ClientId::new(client_id.to_string()),
+    #[test]
+    fn treats_everything_as_new_when_nothing_is_granted() {
+        let diff = diff_scopes(&["account:read", "workers:write"], &HashSet::new());
+
+        assert!(diff.already_granted.is_empty());
+        assert_eq!(
+            diff.newly_requested,
+            vec!["account:read".to_string(), "workers:write".to_string()]
+        );
+    }
+}
+
+// Build the oauth2 client from discovered metadata rather than hardcoded endpoints.
+fn build_client(metadata: &AuthServerMetadata) -> Result<BasicClient> {
+    let mut client = BasicClient::new(
+        ClientId::new(get_client_id()),
         None,
-        AuthUrl::new("https://dash.staging.cloudflare.com/oauth2/auth".to_string())
-            .expect("Invalid authorization endpoint URL"),
-        Some(
-            TokenUrl::new("https://dash.staging.cloudflare.com/oauth2/token".to_string())
-                .expect("Invalid token endpoint URL"),
-        ),
-    )
-    .set_redirect_uri(
-        RedirectUrl::new("http://localhost:8976/oauth/callback".to_string())
-            .expect("Invalid redirect URL"),
+        AuthUrl::new(metadata.authorization_endpoint.clone())?,
+        Some(TokenUrl::new(metadata.token_endpoint.clone())?),
     )
     .set_auth_type(AuthType::RequestBody);
 
-    let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+    if let Some(revocation_endpoint) = &metadata.revocation_endpoint {
+        client = client.set_revocation_uri(RevocationUrl::new(revocation_endpoint.clone())?);
+    }
 
-    // Create URL for user with the necessary scopes
-    let mut client_state = client
-        .authorize_url(CsrfToken::new_random)
-        .set_pkce_challenge(pkce_challenge);
+    Ok(client)
+}
 
-    if scopes.is_none() {
-        // User did not provide any scopes
-        for scope in SCOPES_LIST {
-            client_state = client_state.add_scope(Scope::new(scope.to_string()));
-        }
-    } else {
-        // User did provide some scopes
+pub async fn run(scopes: Option<&[&str]>) -> Result<GlobalUser> {
+    let metadata = discover_metadata_async().await?;
+
+    // Union the requested scopes with whatever the stored token already holds, so
+    // asking for one extra scope doesn't silently drop the rest of the grant. Drop any
+    // previously-granted scope the server no longer advertises, so a scope Cloudflare
+    // has since retired doesn't permanently fail validation on every future login.
+    let mut scopes_to_request = previously_granted_scopes();
+    if !metadata.scopes_supported.is_empty() {
+        let supported: HashSet<&str> = metadata
+            .scopes_supported
+            .iter()
+            .map(String::as_str)
+            .collect();
+        scopes_to_request.retain(|scope| supported.contains(scope.as_str()));
+    }
+    if let Some(scopes) = scopes {
         let valid_scopes: HashSet<&str> = HashSet::from_iter(SCOPES_LIST.iter().cloned());
-        for scope in scopes.unwrap() {
-            if valid_scopes.contains(scope) {
-                client_state = client_state.add_scope(Scope::new(scope.to_string()));
-            } else {
+        for scope in scopes {
+            if !valid_scopes.contains(scope) {
                 anyhow::bail!("Invalid scope has been provided: {}", scope)
             }
         }
+        let diff = diff_requested_scopes(scopes);
+        if !diff.already_granted.is_empty() {
+            println!(
+                "Already granted, no change needed: {}",
+                diff.already_granted.join(", ")
+            );
+        }
+        if !diff.newly_requested.is_empty() {
+            println!("Requesting new scopes: {}", diff.newly_requested.join(", "));
+        }
+        scopes_to_request.extend(scopes.iter().map(|s| s.to_string()));
+    } else {
+        scopes_to_request.extend(SCOPES_LIST.iter().map(|s| s.to_string()));
+    }
+    let requested_scopes: Vec<String> = scopes_to_request.into_iter().collect();
+    let requested_scope_strs: Vec<&str> = requested_scopes.iter().map(String::as_str).collect();
+    validate_scopes(&metadata, &requested_scope_strs)?;
+
+    // Bind the callback listener before building the authorize URL so the
+    // OS-assigned port can be plugged into the redirect URI up front.
+    let (listener, port) = bind_callback_listener()?;
+    let redirect_uri = format!("http://127.0.0.1:{}/oauth/callback", port);
+    let client = build_client(&metadata)?
+        .set_redirect_uri(RedirectUrl::new(redirect_uri).expect("Invalid redirect URL"));
+
+    let (pkce_challenge, pkce_verifier) = PkceCodeChallenge::new_random_sha256();
+
+    // Create URL for user with the union of requested and previously-granted scopes
+    let mut client_state = client
+        .authorize_url(CsrfToken::new_random)
+        .set_pkce_challenge(pkce_challenge);
+    for scope in &requested_scopes {
+        client_state = client_state.add_scope(Scope::new(scope.clone()));
     }
     let (auth_url, csrf_state) = client_state.url();
 
@@ -189,7 +442,7 @@ pub fn run(scopes: Option<&[&str]>) -> Result<()> {
     open_browser(auth_url.as_str())?;
 
     // Get authorization code and CSRF state from local HTTP server
-    let params_values = match block_on(http_server_get_params()) {
+    let params_values = match http_server_get_params(listener, port).await {
         Ok(params) => params,
         Err(_) => anyhow::bail!("Failed to receive authorization code from local HTTP server"),
     };
@@ -229,17 +482,293 @@ pub fn run(scopes: Option<&[&str]>) -> Result<()> {
     let token_response = client
         .exchange_code(AuthorizationCode::new(auth_code.to_string()))
         .set_pkce_verifier(pkce_verifier)
-        .request(http_client)
+        .request_async(async_http_client)
+        .await
         .expect("Failed to retrieve access token");
 
-    // Configure user with new token
-    let user = GlobalUser::TokenAuth {
-        token_type: TokenType::Oauth,
-        value: TokenResponse::access_token(&token_response)
-            .secret()
-            .to_string(),
+    let user = token_response_to_user(&token_response, &requested_scopes);
+    global_config(&user, false)?;
+
+    Ok(user)
+}
+
+// Turn a token endpoint response into the `GlobalUser` we persist, capturing the
+// refresh token and an absolute expiry alongside the access token so callers can
+// refresh transparently instead of waiting for a 401. `requested_scopes` is used as
+// the granted set when the server's response doesn't echo scopes back explicitly.
+fn token_response_to_user<TR: TokenResponse<oauth2::basic::BasicTokenType>>(
+    token_response: &TR,
+    requested_scopes: &[String],
+) -> GlobalUser {
+    let expires_at = unix_now()
+        + token_response
+            .expires_in()
+            .unwrap_or_else(|| Duration::from_secs(0))
+            .as_secs();
+
+    let granted_scopes = token_response
+        .scopes()
+        .map(|scopes| scopes.iter().map(|s| s.to_string()).collect())
+        .unwrap_or_else(|| requested_scopes.to_vec());
+
+    GlobalUser::TokenAuth {
+        token_type: TokenType::Oauth {
+            refresh_token: token_response
+                .refresh_token()
+                .map(|rt| rt.secret().to_string()),
+            expires_at,
+            granted_scopes,
+        },
+        value: token_response.access_token().secret().to_string(),
+    }
+}
+
+#[cfg(test)]
+mod token_response_conversion_tests {
+    use super::*;
+    use oauth2::basic::BasicTokenType;
+    use oauth2::{AccessToken, EmptyExtraTokenFields, StandardTokenResponse};
+
+    fn build_response(
+        scopes: Option<Vec<Scope>>,
+    ) -> StandardTokenResponse<EmptyExtraTokenFields, BasicTokenType> {
+        let mut response = StandardTokenResponse::new(
+            AccessToken::new("access-token".to_string()),
+            BasicTokenType::Bearer,
+            EmptyExtraTokenFields {},
+        );
+        response.set_scopes(scopes);
+        response
+    }
+
+    #[test]
+    fn falls_back_to_requested_scopes_when_response_omits_scopes() {
+        let response = build_response(None);
+        let requested = vec!["account:read".to_string(), "workers:write".to_string()];
+
+        let user = token_response_to_user(&response, &requested);
+
+        match user {
+            GlobalUser::TokenAuth {
+                token_type: TokenType::Oauth { granted_scopes, .. },
+                ..
+            } => assert_eq!(granted_scopes, requested),
+            _ => panic!("expected an OAuth TokenAuth user"),
+        }
+    }
+
+    #[test]
+    fn uses_scopes_from_the_response_when_present() {
+        let response = build_response(Some(vec![Scope::new("zone:read".to_string())]));
+        let requested = vec!["account:read".to_string()];
+
+        let user = token_response_to_user(&response, &requested);
+
+        match user {
+            GlobalUser::TokenAuth {
+                token_type: TokenType::Oauth { granted_scopes, .. },
+                ..
+            } => assert_eq!(granted_scopes, vec!["zone:read".to_string()]),
+            _ => panic!("expected an OAuth TokenAuth user"),
+        }
+    }
+}
+
+/// Revoke a stored OAuth credential per RFC 7009 and remove it from the global config.
+/// Both the access token and, if present, the refresh token are sent to Cloudflare's
+/// revocation endpoint so the session can't be replayed server-side; `wrangler config`
+/// logins (not OAuth) have nothing to revoke and just have their credential removed.
+pub fn logout(user: &GlobalUser) -> Result<()> {
+    if let GlobalUser::TokenAuth {
+        token_type: TokenType::Oauth { refresh_token, .. },
+        value: access_token,
+    } = user
+    {
+        // Revocation is best-effort: a user who's offline, or hitting a staging/prod
+        // metadata hiccup, should still be able to clear their local credential rather
+        // than getting stuck with a stale token because the server is unreachable.
+        match discover_metadata().and_then(|metadata| build_client(&metadata)) {
+            Ok(client) => {
+                revoke(
+                    &client,
+                    StandardRevocableToken::AccessToken(oauth2::AccessToken::new(
+                        access_token.clone(),
+                    )),
+                );
+                if let Some(refresh_token) = refresh_token {
+                    revoke(
+                        &client,
+                        StandardRevocableToken::RefreshToken(RefreshToken::new(
+                            refresh_token.clone(),
+                        )),
+                    );
+                }
+            }
+            Err(e) => log::debug!(
+                "couldn't reach the OAuth server to revoke the token, skipping revocation: {}",
+                e
+            ),
+        }
+    }
+
+    delete_global_config()?;
+
+    Ok(())
+}
+
+// Best-effort revocation: a token that's already invalid (expired, or revoked by a
+// previous `logout`) still leaves us in the desired end state, so we don't surface
+// the request failure as an error to the caller. If the server didn't advertise a
+// revocation endpoint there's nothing to call, so we just skip it.
+fn revoke(client: &BasicClient, token: StandardRevocableToken) {
+    let request = match client.revoke_token(token) {
+        Ok(request) => request,
+        Err(e) => {
+            log::debug!("no revocation endpoint configured, skipping revoke: {}", e);
+            return;
+        }
     };
+    if let Err(e) = request.request(http_client) {
+        log::debug!(
+            "token revocation request failed (treating as success): {}",
+            e
+        );
+    }
+}
+
+/// Exchange a stored refresh token for a new access token, rewriting the global config
+/// with the renewed credentials. If the refresh token itself has been revoked or has
+/// expired, falls back to the interactive `run` login flow rather than erroring out.
+///
+/// `run`'s callback server is built entirely on the caller's Tokio runtime, so the
+/// fallback awaits it directly instead of reaching for a separate blocking executor
+/// that wouldn't share that runtime context.
+pub async fn refresh_token(user: &GlobalUser) -> Result<GlobalUser> {
+    let (refresh_token, granted_scopes) = match user {
+        GlobalUser::TokenAuth {
+            token_type:
+                TokenType::Oauth {
+                    refresh_token: Some(refresh_token),
+                    granted_scopes,
+                    ..
+                },
+            ..
+        } => (refresh_token.clone(), granted_scopes.clone()),
+        _ => return run(None).await,
+    };
+
+    let client = build_client(&discover_metadata_async().await?)?;
+
+    let token_response = match client
+        .exchange_refresh_token(&RefreshToken::new(refresh_token))
+        .request_async(async_http_client)
+        .await
+    {
+        Ok(token_response) => token_response,
+        // The refresh token itself was rejected (revoked/expired); the only way
+        // forward is a fresh interactive login.
+        Err(_) => return run(None).await,
+    };
+
+    // A refresh doesn't change the grant, so fall back to whatever scopes were
+    // already held if the server doesn't echo them back on this response.
+    let user = token_response_to_user(&token_response, &granted_scopes);
     global_config(&user, false)?;
 
+    Ok(user)
+}
+
+/// Return a `GlobalUser` guaranteed to carry a live access token, transparently
+/// refreshing the stored OAuth credential when it's within `MIN_TIME_LEFT` seconds of
+/// expiring (or already expired) rather than letting the caller's API call 401.
+/// Callers making API requests on behalf of the stored session should route through
+/// this instead of reading the stored token directly.
+pub async fn get_valid_access_token(user: &GlobalUser) -> Result<GlobalUser> {
+    match user {
+        GlobalUser::TokenAuth {
+            token_type: TokenType::Oauth { expires_at, .. },
+            ..
+        } if token_is_expired(*expires_at) => refresh_token(user).await,
+        _ => Ok(user.clone()),
+    }
+}
+
+// RFC 7662 token introspection response. `scope` is a single space-delimited string
+// per the spec, not a list, so callers split it themselves.
+#[derive(Deserialize)]
+pub struct TokenIntrospection {
+    pub active: bool,
+    #[serde(default)]
+    pub scope: String,
+    pub exp: Option<u64>,
+    pub sub: Option<String>,
+    pub client_id: Option<String>,
+}
+
+/// POST the stored access token to the server's introspection endpoint (RFC 7662) to
+/// check whether it's still valid and what scopes/expiry it carries.
+pub fn introspect_token(access_token: &str) -> Result<TokenIntrospection> {
+    let metadata = discover_metadata()?;
+    let introspection_endpoint = metadata.introspection_endpoint.ok_or_else(|| {
+        anyhow::anyhow!(
+            "{} does not advertise an introspection endpoint",
+            oauth_base_url()
+        )
+    })?;
+
+    let introspection = reqwest::blocking::Client::new()
+        .post(&introspection_endpoint)
+        .form(&[
+            ("token", access_token),
+            ("token_type_hint", "access_token"),
+            ("client_id", &get_client_id()),
+        ])
+        .send()?
+        .error_for_status()?
+        .json::<TokenIntrospection>()?;
+
+    Ok(introspection)
+}
+
+/// A `wrangler whoami`-style summary of the active session: whether it's still valid,
+/// which scopes it carries, and how long until it expires.
+pub struct SessionReport {
+    pub active: bool,
+    pub scopes: Vec<String>,
+    pub seconds_until_expiry: Option<i64>,
+    pub subject: Option<String>,
+}
+
+/// Report on the session backing `user`. Only OAuth tokens can be introspected; other
+/// auth methods (e.g. `wrangler config`'s API key) have no endpoint to ask.
+pub fn whoami(user: &GlobalUser) -> Result<SessionReport> {
+    let access_token = match user {
+        GlobalUser::TokenAuth {
+            token_type: TokenType::Oauth { .. },
+            value,
+        } => value,
+        _ => anyhow::bail!("Session introspection is only available for `wrangler login` sessions"),
+    };
+
+    let introspection = introspect_token(access_token)?;
+
+    Ok(SessionReport {
+        active: introspection.active,
+        scopes: introspection
+            .scope
+            .split_whitespace()
+            .map(str::to_string)
+            .collect(),
+        seconds_until_expiry: introspection.exp.map(|exp| exp as i64 - unix_now() as i64),
+        subject: introspection.sub.or(introspection.client_id),
+    })
+}
+
+/// Pre-flight check to run before a long operation: fail fast with a clear message
+/// instead of letting downstream API calls 401 opaquely partway through.
+pub fn ensure_session_active(user: &GlobalUser) -> Result<()> {
+    if !whoami(user)?.active {
+        anyhow::bail!("Your session has expired. Run `wrangler login` to log in again.");
+    }
     Ok(())
 }